@@ -0,0 +1,661 @@
+//! Builds and dispatches the outgoing HTTP request to whichever LLM provider
+//! a workspace is configured for (`GlobalChatSettings::source` in
+//! `settings.rs`), translating the OpenAI-shaped request body this route
+//! accepts into each provider's own auth scheme and, where it differs
+//! (Anthropic, Gemini), its own message schema.
+
+use actix_web::web::{self, Data};
+use actix_web::HttpResponse;
+use index_scheduler::IndexScheduler;
+use meilisearch_types::error::{Code, ResponseError};
+use meilisearch_types::features::{
+    ChatCompletionSettings, ChatCompletionSource as DbChatCompletionSource,
+};
+use meilisearch_types::keys::actions;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::extractors::authentication::policies::ActionPolicy;
+use crate::extractors::authentication::GuardedData;
+use crate::extractors::sequential_extractor::SeqHandler;
+
+use super::{limits, secrets, stats, ChatsParam};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::post().to(SeqHandler(chat_completions))));
+}
+
+/// The request body accepted by `POST /{workspace_uid}/chat/completions`.
+/// Always OpenAI-shaped on the wire; [`build_provider_request`] translates it
+/// into whatever the configured provider actually expects.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<Value>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+/// Counts how many tool-call round trips already happened in this
+/// conversation, for enforcing `max_tool_call_rounds`. Derived from the
+/// assistant's own messages rather than a client-supplied counter: `messages`
+/// is the full conversation history resent with every request, so a caller
+/// cannot under-report it without also dropping the tool-call messages
+/// themselves, which would break the conversation.
+fn count_prior_tool_call_rounds(messages: &[Value]) -> u32 {
+    messages
+        .iter()
+        .filter(|message| {
+            message.get("role").and_then(Value::as_str) == Some("assistant")
+                && message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .is_some_and(|calls| !calls.is_empty())
+        })
+        .count() as u32
+}
+
+async fn chat_completions(
+    index_scheduler: GuardedData<ActionPolicy<{ actions::CHATS_GET }>, Data<IndexScheduler>>,
+    chats_param: web::Path<ChatsParam>,
+    web::Json(body): web::Json<ChatCompletionRequest>,
+) -> Result<HttpResponse, ResponseError> {
+    let ChatsParam { workspace_uid } = chats_param.into_inner();
+    index_scheduler.features().check_chat_completions("Using the /chats completions route")?;
+
+    let rtxn = index_scheduler.read_txn()?;
+    let settings = match index_scheduler.chat_settings(&rtxn, &workspace_uid)? {
+        Some(settings) => settings,
+        None => {
+            return Err(ResponseError::from_msg(
+                format!("Chat `{workspace_uid}` not found"),
+                Code::ChatWorkspaceNotFound,
+            ))
+        }
+    };
+    drop(rtxn);
+
+    if let Some(requests_per_minute) = settings.requests_per_minute {
+        limits::acquire_request_slot(&workspace_uid, requests_per_minute)?;
+    }
+    limits::ensure_model_allowed(&settings, &body.model)?;
+    let rounds_so_far = count_prior_tool_call_rounds(&body.messages);
+    limits::ensure_tool_call_rounds_allowed(&settings, rounds_so_far)?;
+    let max_tokens = limits::clamp_max_tokens(&settings, body.max_tokens);
+
+    // `settings.api_key` is sealed at rest (see `secrets.rs`); open it here,
+    // right before it is used to build the outgoing provider request, so the
+    // plaintext never lives longer than this request.
+    let api_key = settings
+        .api_key
+        .as_deref()
+        .map(|sealed| secrets::open_api_key(&index_scheduler, sealed))
+        .transpose()?;
+
+    let (url, headers, payload) = build_provider_request(&settings, api_key.as_deref(), &body, max_tokens)?;
+
+    let send_result = reqwest::Client::new().post(url).headers(headers).json(&payload).send().await;
+
+    let response = match send_result {
+        Ok(response) => response,
+        Err(err) => {
+            record_usage(&workspace_uid, stats::ChatUsageEvent { is_error: true, ..Default::default() });
+            return Err(ResponseError::from_msg(
+                format!("Failed to reach the chat completion provider: {err}"),
+                Code::ChatCompletionProviderError,
+            ));
+        }
+    };
+
+    let status = response.status();
+    let body_json: Value = match response.json().await {
+        Ok(body_json) => body_json,
+        Err(err) => {
+            record_usage(&workspace_uid, stats::ChatUsageEvent { is_error: true, ..Default::default() });
+            return Err(ResponseError::from_msg(
+                format!("Failed to parse the chat completion provider's response: {err}"),
+                Code::ChatCompletionProviderError,
+            ));
+        }
+    };
+
+    let (prompt_tokens, completion_tokens, tool_call_count) = extract_usage(settings.source, &body_json);
+    let usage = stats::ChatUsageEvent {
+        prompt_tokens,
+        completion_tokens,
+        tool_call_count,
+        is_error: !status.is_success(),
+    };
+    record_usage(&workspace_uid, usage);
+
+    if !status.is_success() {
+        return Err(ResponseError::from_msg(
+            format!("Chat completion provider returned an error: {body_json}"),
+            Code::ChatCompletionProviderError,
+        ));
+    }
+
+    Ok(HttpResponse::Ok().json(normalize_response(settings.source, body_json)))
+}
+
+/// Translates a provider's native response back into the OpenAI-shaped
+/// `choices[0].message` contract this route is documented to speak, so a
+/// client doesn't see a different schema depending on `settings.source`.
+/// OpenAI-compatible sources (`OpenAi`, `AzureOpenAi`, `Mistral`,
+/// `OpenAiCompatible`) already speak that shape and pass through unchanged.
+fn normalize_response(source: DbChatCompletionSource, body: Value) -> Value {
+    match source {
+        DbChatCompletionSource::Anthropic => normalize_anthropic_response(body),
+        DbChatCompletionSource::Gemini => normalize_gemini_response(body),
+        DbChatCompletionSource::OpenAi
+        | DbChatCompletionSource::AzureOpenAi
+        | DbChatCompletionSource::Mistral
+        | DbChatCompletionSource::OpenAiCompatible => body,
+    }
+}
+
+/// Anthropic's Messages API replies with a `content` array of typed blocks
+/// (`text`, `tool_use`, ...) instead of `choices[0].message`; fold it back
+/// into that shape, turning `tool_use` blocks into OpenAI-style `tool_calls`.
+fn normalize_anthropic_response(body: Value) -> Value {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    if let Some(blocks) = body.get("content").and_then(Value::as_array) {
+        for block in blocks {
+            match block.get("type").and_then(Value::as_str) {
+                Some("text") => {
+                    if let Some(chunk) = block.get("text").and_then(Value::as_str) {
+                        text.push_str(chunk);
+                    }
+                }
+                Some("tool_use") => tool_calls.push(serde_json::json!({
+                    "id": block.get("id").cloned().unwrap_or(Value::Null),
+                    "type": "function",
+                    "function": {
+                        "name": block.get("name").cloned().unwrap_or(Value::Null),
+                        "arguments": block.get("input").cloned().unwrap_or(Value::Null).to_string(),
+                    },
+                })),
+                _ => {}
+            }
+        }
+    }
+
+    let mut message = serde_json::json!({ "role": "assistant", "content": text });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+
+    serde_json::json!({
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": body.get("stop_reason").cloned().unwrap_or(Value::Null),
+        }],
+        "usage": {
+            "prompt_tokens": body.pointer("/usage/input_tokens").cloned().unwrap_or(Value::from(0)),
+            "completion_tokens": body.pointer("/usage/output_tokens").cloned().unwrap_or(Value::from(0)),
+        },
+    })
+}
+
+/// Gemini's `generateContent` API replies with `candidates[0].content.parts`
+/// instead of `choices[0].message`; fold it back into that shape, turning
+/// `functionCall` parts into OpenAI-style `tool_calls`.
+fn normalize_gemini_response(body: Value) -> Value {
+    let candidate = body.pointer("/candidates/0");
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    if let Some(parts) = candidate.and_then(|c| c.pointer("/content/parts")).and_then(Value::as_array)
+    {
+        for part in parts {
+            if let Some(chunk) = part.get("text").and_then(Value::as_str) {
+                text.push_str(chunk);
+            } else if let Some(call) = part.get("functionCall") {
+                tool_calls.push(serde_json::json!({
+                    "id": format!("call_{}", tool_calls.len()),
+                    "type": "function",
+                    "function": {
+                        "name": call.get("name").cloned().unwrap_or(Value::Null),
+                        "arguments": call.get("args").cloned().unwrap_or(Value::Null).to_string(),
+                    },
+                }));
+            }
+        }
+    }
+
+    let mut message = serde_json::json!({ "role": "assistant", "content": text });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+
+    serde_json::json!({
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": candidate.and_then(|c| c.get("finishReason")).cloned().unwrap_or(Value::Null),
+        }],
+        "usage": {
+            "prompt_tokens": body.pointer("/usageMetadata/promptTokenCount").cloned().unwrap_or(Value::from(0)),
+            "completion_tokens": body.pointer("/usageMetadata/candidatesTokenCount").cloned().unwrap_or(Value::from(0)),
+        },
+    })
+}
+
+/// Folds one provider call's outcome into the workspace's rolling usage counters.
+/// See [`super::usage_store`] for why this is a process-lifetime store rather
+/// than an `IndexScheduler` call.
+fn record_usage(workspace_uid: &str, usage: stats::ChatUsageEvent) {
+    super::usage_store::record(workspace_uid, usage);
+}
+
+/// Extracts `(prompt_tokens, completion_tokens, tool_call_count)` from a
+/// provider's raw response. Each source reports usage and tool calls in its
+/// own shape, so this reads the native response, not the OpenAI-normalized
+/// one [`normalize_response`] builds for the caller.
+fn extract_usage(source: DbChatCompletionSource, body: &Value) -> (u64, u64, u64) {
+    match source {
+        DbChatCompletionSource::Anthropic => (
+            body.pointer("/usage/input_tokens").and_then(Value::as_u64).unwrap_or(0),
+            body.pointer("/usage/output_tokens").and_then(Value::as_u64).unwrap_or(0),
+            body.get("content")
+                .and_then(Value::as_array)
+                .map(|blocks| {
+                    blocks
+                        .iter()
+                        .filter(|block| {
+                            block.get("type").and_then(Value::as_str) == Some("tool_use")
+                                && block.get("name").and_then(Value::as_str)
+                                    == Some(super::MEILI_SEARCH_IN_INDEX_FUNCTION_NAME)
+                        })
+                        .count() as u64
+                })
+                .unwrap_or(0),
+        ),
+        DbChatCompletionSource::Gemini => (
+            body.pointer("/usageMetadata/promptTokenCount").and_then(Value::as_u64).unwrap_or(0),
+            body.pointer("/usageMetadata/candidatesTokenCount").and_then(Value::as_u64).unwrap_or(0),
+            body.pointer("/candidates/0/content/parts")
+                .and_then(Value::as_array)
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter(|part| {
+                            part.pointer("/functionCall/name").and_then(Value::as_str)
+                                == Some(super::MEILI_SEARCH_IN_INDEX_FUNCTION_NAME)
+                        })
+                        .count() as u64
+                })
+                .unwrap_or(0),
+        ),
+        DbChatCompletionSource::OpenAi
+        | DbChatCompletionSource::AzureOpenAi
+        | DbChatCompletionSource::Mistral
+        | DbChatCompletionSource::OpenAiCompatible => (
+            body.pointer("/usage/prompt_tokens").and_then(Value::as_u64).unwrap_or(0),
+            body.pointer("/usage/completion_tokens").and_then(Value::as_u64).unwrap_or(0),
+            count_tool_calls(body),
+        ),
+    }
+}
+
+/// Counts invocations of the internal search tool ([`super::MEILI_SEARCH_IN_INDEX_FUNCTION_NAME`])
+/// in an OpenAI-shaped completion response, for the OpenAI-compatible sources.
+fn count_tool_calls(response: &Value) -> u64 {
+    response
+        .pointer("/choices/0/message/tool_calls")
+        .and_then(Value::as_array)
+        .map(|calls| {
+            calls
+                .iter()
+                .filter(|call| {
+                    call.pointer("/function/name").and_then(Value::as_str)
+                        == Some(super::MEILI_SEARCH_IN_INDEX_FUNCTION_NAME)
+                })
+                .count() as u64
+        })
+        .unwrap_or(0)
+}
+
+/// Builds the `(url, headers, body)` to send to the provider configured for
+/// `settings.source`. Each provider gets its own auth scheme, and the two
+/// whose message schema differs from OpenAI's (Anthropic, Gemini) get their
+/// own payload shape.
+fn build_provider_request(
+    settings: &ChatCompletionSettings,
+    api_key: Option<&str>,
+    body: &ChatCompletionRequest,
+    max_tokens: Option<u32>,
+) -> Result<(String, HeaderMap, Value), ResponseError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    match settings.source {
+        DbChatCompletionSource::Anthropic => {
+            if let Some(key) = api_key {
+                headers.insert("x-api-key", header_value(key)?);
+            }
+            headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+            let url = settings
+                .base_api
+                .clone()
+                .unwrap_or_else(|| "https://api.anthropic.com/v1/messages".to_string());
+            Ok((url, headers, anthropic_payload(body, max_tokens)))
+        }
+        DbChatCompletionSource::Gemini => {
+            let key = api_key.ok_or_else(|| {
+                ResponseError::from_msg(
+                    "Gemini chat sources require an `apiKey` to be set".to_string(),
+                    Code::InvalidChatCompletionApiKey,
+                )
+            })?;
+            let base_api = settings.base_api.clone().unwrap_or_else(|| {
+                "https://generativelanguage.googleapis.com/v1beta/models".to_string()
+            });
+            let url = format!("{base_api}/{}:generateContent?key={key}", body.model);
+            Ok((url, headers, gemini_payload(body, max_tokens)))
+        }
+        DbChatCompletionSource::AzureOpenAi => {
+            if let Some(key) = api_key {
+                headers.insert("api-key", header_value(key)?);
+            }
+            let base_api = settings.base_api.as_deref().ok_or_else(|| {
+                ResponseError::from_msg(
+                    "Azure OpenAI chat sources require `baseApi` to be set to the resource endpoint"
+                        .to_string(),
+                    Code::InvalidChatCompletionBaseApi,
+                )
+            })?;
+            let deployment_id = settings.deployment_id.as_deref().ok_or_else(|| {
+                ResponseError::from_msg(
+                    "Azure OpenAI chat sources require `deploymentId` to be set".to_string(),
+                    Code::InvalidChatCompletionDeploymentId,
+                )
+            })?;
+            let api_version = settings.api_version.as_deref().unwrap_or("2024-06-01");
+            let url = format!(
+                "{base_api}/openai/deployments/{deployment_id}/chat/completions?api-version={api_version}"
+            );
+            Ok((url, headers, openai_payload(body, max_tokens)))
+        }
+        DbChatCompletionSource::OpenAi => {
+            if let Some(key) = api_key {
+                headers.insert(AUTHORIZATION, bearer(key)?);
+            }
+            let url = settings
+                .base_api
+                .clone()
+                .unwrap_or_else(|| "https://api.openai.com/v1/chat/completions".to_string());
+            Ok((url, headers, openai_payload(body, max_tokens)))
+        }
+        DbChatCompletionSource::Mistral => {
+            if let Some(key) = api_key {
+                headers.insert(AUTHORIZATION, bearer(key)?);
+            }
+            let url = settings
+                .base_api
+                .clone()
+                .unwrap_or_else(|| "https://api.mistral.ai/v1/chat/completions".to_string());
+            Ok((url, headers, openai_payload(body, max_tokens)))
+        }
+        DbChatCompletionSource::OpenAiCompatible => {
+            if let Some(key) = api_key {
+                headers.insert(AUTHORIZATION, bearer(key)?);
+            }
+            let url = settings.base_api.clone().ok_or_else(|| {
+                ResponseError::from_msg(
+                    "`openAiCompatible` chat sources require `baseApi` to be set to the server's endpoint"
+                        .to_string(),
+                    Code::InvalidChatCompletionBaseApi,
+                )
+            })?;
+            Ok((url, headers, openai_payload(body, max_tokens)))
+        }
+    }
+}
+
+fn bearer(key: &str) -> Result<HeaderValue, ResponseError> {
+    header_value(&format!("Bearer {key}"))
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, ResponseError> {
+    HeaderValue::from_str(value).map_err(|_| {
+        ResponseError::from_msg(
+            "Chat completion API key contains characters that cannot be sent in an HTTP header"
+                .to_string(),
+            Code::InvalidChatCompletionApiKey,
+        )
+    })
+}
+
+fn openai_payload(body: &ChatCompletionRequest, max_tokens: Option<u32>) -> Value {
+    let mut payload = serde_json::json!({ "model": body.model, "messages": body.messages });
+    if let Some(max_tokens) = max_tokens {
+        payload["max_tokens"] = Value::from(max_tokens);
+    }
+    payload
+}
+
+/// Anthropic's Messages API pulls the system prompt out of `messages` into
+/// its own top-level field, and always requires `max_tokens`.
+fn anthropic_payload(body: &ChatCompletionRequest, max_tokens: Option<u32>) -> Value {
+    let mut system_prompt = String::new();
+    let mut messages = Vec::with_capacity(body.messages.len());
+    for message in &body.messages {
+        if message.get("role").and_then(Value::as_str) == Some("system") {
+            if let Some(content) = message.get("content").and_then(Value::as_str) {
+                system_prompt.push_str(content);
+            }
+        } else {
+            messages.push(message.clone());
+        }
+    }
+
+    serde_json::json!({
+        "model": body.model,
+        "system": system_prompt,
+        "messages": messages,
+        "max_tokens": max_tokens.unwrap_or(4096),
+    })
+}
+
+/// Gemini's `generateContent` API uses `contents`/`parts` instead of
+/// `messages`, calls the assistant role `model` instead of `assistant`, and
+/// takes `max_tokens` as `generationConfig.maxOutputTokens`.
+fn gemini_payload(body: &ChatCompletionRequest, max_tokens: Option<u32>) -> Value {
+    let contents = body
+        .messages
+        .iter()
+        .filter(|message| message.get("role").and_then(Value::as_str) != Some("system"))
+        .map(|message| {
+            let role = match message.get("role").and_then(Value::as_str) {
+                Some("assistant") => "model",
+                _ => "user",
+            };
+            serde_json::json!({
+                "role": role,
+                "parts": [{ "text": message.get("content").and_then(Value::as_str).unwrap_or_default() }],
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut payload = serde_json::json!({ "contents": contents });
+    if let Some(max_tokens) = max_tokens {
+        payload["generationConfig"] = serde_json::json!({ "maxOutputTokens": max_tokens });
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with_source(source: DbChatCompletionSource) -> ChatCompletionSettings {
+        ChatCompletionSettings { source, ..ChatCompletionSettings::default() }
+    }
+
+    fn sample_request() -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![serde_json::json!({"role": "system", "content": "be terse"}), serde_json::json!({"role": "user", "content": "hello"})],
+            max_tokens: None,
+        }
+    }
+
+    #[test]
+    fn anthropic_uses_its_own_auth_header_and_payload_shape() {
+        let settings = settings_with_source(DbChatCompletionSource::Anthropic);
+        let (url, headers, payload) =
+            build_provider_request(&settings, Some("sk-ant-test"), &sample_request(), None).unwrap();
+
+        assert_eq!(url, "https://api.anthropic.com/v1/messages");
+        assert_eq!(headers.get("x-api-key").unwrap(), "sk-ant-test");
+        assert!(headers.get(AUTHORIZATION).is_none());
+        assert_eq!(payload["system"], "be terse");
+        assert_eq!(payload["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn azure_requires_deployment_id_and_builds_deployment_scoped_url() {
+        let mut settings = settings_with_source(DbChatCompletionSource::AzureOpenAi);
+        settings.base_api = Some("https://my-resource.openai.azure.com".to_string());
+        settings.deployment_id = Some("my-gpt4-deployment".to_string());
+
+        let (url, headers, _) =
+            build_provider_request(&settings, Some("azure-key"), &sample_request(), None).unwrap();
+
+        assert!(url.contains("/openai/deployments/my-gpt4-deployment/chat/completions"));
+        assert_eq!(headers.get("api-key").unwrap(), "azure-key");
+
+        let mut missing_deployment = settings_with_source(DbChatCompletionSource::AzureOpenAi);
+        missing_deployment.base_api = Some("https://my-resource.openai.azure.com".to_string());
+        assert!(build_provider_request(&missing_deployment, Some("azure-key"), &sample_request(), None)
+            .is_err());
+    }
+
+    #[test]
+    fn openai_defaults_to_bearer_auth_and_public_endpoint() {
+        let settings = settings_with_source(DbChatCompletionSource::OpenAi);
+        let (url, headers, _) =
+            build_provider_request(&settings, Some("sk-test"), &sample_request(), None).unwrap();
+
+        assert_eq!(url, "https://api.openai.com/v1/chat/completions");
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Bearer sk-test");
+    }
+
+    #[test]
+    fn counts_only_tool_calls_to_the_internal_search_function() {
+        let response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "tool_calls": [
+                        {"function": {"name": "_meiliSearchInIndex"}},
+                        {"function": {"name": "_meiliSearchInIndex"}},
+                        {"function": {"name": "someOtherTool"}},
+                    ]
+                }
+            }]
+        });
+
+        assert_eq!(count_tool_calls(&response), 2);
+    }
+
+    #[test]
+    fn counts_zero_tool_calls_when_absent() {
+        let response = serde_json::json!({"choices": [{"message": {}}]});
+        assert_eq!(count_tool_calls(&response), 0);
+    }
+
+    #[test]
+    fn anthropic_response_is_normalized_to_the_openai_shape() {
+        let raw = serde_json::json!({
+            "content": [
+                {"type": "text", "text": "looking that up"},
+                {"type": "tool_use", "id": "toolu_1", "name": "_meiliSearchInIndex", "input": {"q": "hi"}},
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 12, "output_tokens": 34},
+        });
+
+        let normalized = normalize_anthropic_response(raw);
+
+        assert_eq!(normalized["choices"][0]["message"]["content"], "looking that up");
+        assert_eq!(normalized["choices"][0]["message"]["tool_calls"][0]["function"]["name"], "_meiliSearchInIndex");
+        assert_eq!(normalized["usage"]["prompt_tokens"], 12);
+        assert_eq!(normalized["usage"]["completion_tokens"], 34);
+    }
+
+    #[test]
+    fn gemini_response_is_normalized_to_the_openai_shape() {
+        let raw = serde_json::json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "hello there"}]},
+                "finishReason": "STOP",
+            }],
+            "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 7},
+        });
+
+        let normalized = normalize_gemini_response(raw);
+
+        assert_eq!(normalized["choices"][0]["message"]["content"], "hello there");
+        assert_eq!(normalized["choices"][0]["message"]["role"], "assistant");
+        assert_eq!(normalized["usage"]["prompt_tokens"], 5);
+        assert_eq!(normalized["usage"]["completion_tokens"], 7);
+    }
+
+    #[test]
+    fn openai_shaped_sources_pass_through_the_response_unchanged() {
+        let raw = serde_json::json!({"choices": [{"message": {"role": "assistant", "content": "hi"}}]});
+        assert_eq!(normalize_response(DbChatCompletionSource::OpenAi, raw.clone()), raw);
+    }
+
+    #[test]
+    fn anthropic_usage_is_read_from_its_own_response_shape() {
+        let raw = serde_json::json!({
+            "content": [
+                {"type": "tool_use", "name": "_meiliSearchInIndex"},
+                {"type": "text", "text": "done"},
+            ],
+            "usage": {"input_tokens": 3, "output_tokens": 4},
+        });
+
+        assert_eq!(extract_usage(DbChatCompletionSource::Anthropic, &raw), (3, 4, 1));
+    }
+
+    #[test]
+    fn gemini_usage_is_read_from_its_own_response_shape() {
+        let raw = serde_json::json!({
+            "candidates": [{"content": {"parts": [{"functionCall": {"name": "_meiliSearchInIndex"}}]}}],
+            "usageMetadata": {"promptTokenCount": 9, "candidatesTokenCount": 1},
+        });
+
+        assert_eq!(extract_usage(DbChatCompletionSource::Gemini, &raw), (9, 1, 1));
+    }
+
+    #[test]
+    fn openai_usage_falls_back_to_zero_when_absent() {
+        let raw = serde_json::json!({"choices": [{"message": {}}]});
+        assert_eq!(extract_usage(DbChatCompletionSource::OpenAi, &raw), (0, 0, 0));
+    }
+
+    #[test]
+    fn tool_call_rounds_are_counted_from_assistant_messages_not_trusted_input() {
+        let messages = vec![
+            serde_json::json!({"role": "user", "content": "search for cats"}),
+            serde_json::json!({"role": "assistant", "tool_calls": [{"function": {"name": "_meiliSearchInIndex"}}]}),
+            serde_json::json!({"role": "tool", "content": "[]"}),
+            serde_json::json!({"role": "assistant", "content": "no cats found"}),
+            serde_json::json!({"role": "user", "content": "try dogs"}),
+            serde_json::json!({"role": "assistant", "tool_calls": [{"function": {"name": "_meiliSearchInIndex"}}]}),
+        ];
+
+        assert_eq!(count_prior_tool_call_rounds(&messages), 2);
+    }
+
+    #[test]
+    fn assistant_messages_without_tool_calls_do_not_count_as_a_round() {
+        let messages = vec![serde_json::json!({"role": "assistant", "content": "hi"})];
+        assert_eq!(count_prior_tool_call_rounds(&messages), 0);
+    }
+}
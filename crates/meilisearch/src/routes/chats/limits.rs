@@ -0,0 +1,191 @@
+//! Enforcement of the per-workspace request limits configured through
+//! `GlobalChatSettings` (see `settings.rs`): `chat_completions` calls
+//! [`ensure_model_allowed`], [`clamp_max_tokens`] and
+//! [`ensure_tool_call_rounds_allowed`] against the loaded
+//! `ChatCompletionSettings` before it builds the provider request, and
+//! [`acquire_request_slot`] against the workspace's [`RateLimiter`] before
+//! that, rejecting the request instead of dispatching it when a limit is hit.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use dashmap::DashMap;
+use meilisearch_types::error::{Code, ResponseError};
+use meilisearch_types::features::ChatCompletionSettings;
+use once_cell::sync::Lazy;
+
+/// Rejects `requested_model` if the workspace restricts which models may be used.
+pub fn ensure_model_allowed(
+    settings: &ChatCompletionSettings,
+    requested_model: &str,
+) -> Result<(), ResponseError> {
+    match &settings.allowed_models {
+        Some(allowed) if !allowed.is_empty() && !allowed.iter().any(|m| m == requested_model) => {
+            Err(ResponseError::from_msg(
+                format!(
+                    "Model `{requested_model}` is not in this workspace's allowed model list"
+                ),
+                Code::InvalidChatCompletionAllowedModels,
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Clamps a caller-requested `max_tokens` to the workspace ceiling, if any is configured.
+pub fn clamp_max_tokens(settings: &ChatCompletionSettings, requested: Option<u32>) -> Option<u32> {
+    match (settings.max_tokens, requested) {
+        (Some(ceiling), Some(requested)) => Some(requested.min(ceiling)),
+        (Some(ceiling), None) => Some(ceiling),
+        (None, requested) => requested,
+    }
+}
+
+/// Rejects the request once `rounds_so_far` reaches the workspace's maximum
+/// number of `_meiliSearchInIndex` tool-call rounds for a single conversation.
+pub fn ensure_tool_call_rounds_allowed(
+    settings: &ChatCompletionSettings,
+    rounds_so_far: u32,
+) -> Result<(), ResponseError> {
+    if let Some(max_rounds) = settings.max_tool_call_rounds {
+        if rounds_so_far >= max_rounds {
+            return Err(ResponseError::from_msg(
+                format!(
+                    "This conversation reached the workspace's limit of {max_rounds} search tool-call rounds"
+                ),
+                Code::ChatCompletionToolCallLimitExceeded,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A per-workspace token-bucket limiter enforcing `requests_per_minute`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self { capacity: requests_per_minute, tokens: requests_per_minute as f64, last_refill: Instant::now() }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Consumes one token, refilling the bucket based on elapsed time first.
+    /// Returns a 429-mapped error when the workspace is over its rate limit.
+    pub fn try_acquire(&mut self) -> Result<(), ResponseError> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * (self.capacity as f64 / 60.0))
+            .min(self.capacity as f64);
+
+        if self.tokens < 1.0 {
+            return Err(ResponseError::from_msg(
+                "Too many chat completion requests for this workspace, please slow down"
+                    .to_string(),
+                Code::ChatCompletionRateLimited,
+            ));
+        }
+
+        self.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Process-lifetime registry of per-workspace rate limiters, keyed by
+/// workspace uid. `chat_completions` has no other place to stash per-tenant
+/// state between requests, so this lives behind a lazily-initialized static
+/// rather than actix app data.
+static RATE_LIMITERS: Lazy<DashMap<String, Mutex<RateLimiter>>> = Lazy::new(DashMap::new);
+
+/// Consumes one request slot for `workspace_uid` against its configured
+/// `requests_per_minute`, creating the workspace's limiter on first use and
+/// resetting it if the configured rate has since changed.
+pub fn acquire_request_slot(workspace_uid: &str, requests_per_minute: u32) -> Result<(), ResponseError> {
+    let limiter = RATE_LIMITERS
+        .entry(workspace_uid.to_string())
+        .or_insert_with(|| Mutex::new(RateLimiter::new(requests_per_minute)));
+    let mut limiter = limiter.lock().unwrap();
+    if limiter.capacity() != requests_per_minute {
+        *limiter = RateLimiter::new(requests_per_minute);
+    }
+    limiter.try_acquire()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ChatCompletionSettings {
+        ChatCompletionSettings::default()
+    }
+
+    #[test]
+    fn model_allow_list_rejects_models_outside_the_list() {
+        let mut settings = settings();
+        settings.allowed_models = Some(vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()]);
+
+        assert!(ensure_model_allowed(&settings, "gpt-4o").is_ok());
+        assert!(ensure_model_allowed(&settings, "gpt-3.5-turbo").is_err());
+    }
+
+    #[test]
+    fn empty_or_unset_allow_list_allows_any_model() {
+        let settings = settings();
+        assert!(ensure_model_allowed(&settings, "anything").is_ok());
+
+        let mut empty_list = settings.clone();
+        empty_list.allowed_models = Some(vec![]);
+        assert!(ensure_model_allowed(&empty_list, "anything").is_ok());
+    }
+
+    #[test]
+    fn clamp_max_tokens_caps_at_the_configured_ceiling() {
+        let mut settings = settings();
+        settings.max_tokens = Some(100);
+
+        assert_eq!(clamp_max_tokens(&settings, Some(500)), Some(100));
+        assert_eq!(clamp_max_tokens(&settings, Some(50)), Some(50));
+        assert_eq!(clamp_max_tokens(&settings, None), Some(100));
+    }
+
+    #[test]
+    fn clamp_max_tokens_passes_through_when_unconfigured() {
+        assert_eq!(clamp_max_tokens(&settings(), Some(500)), Some(500));
+        assert_eq!(clamp_max_tokens(&settings(), None), None);
+    }
+
+    #[test]
+    fn tool_call_rounds_are_rejected_once_the_ceiling_is_reached() {
+        let mut settings = settings();
+        settings.max_tool_call_rounds = Some(3);
+
+        assert!(ensure_tool_call_rounds_allowed(&settings, 0).is_ok());
+        assert!(ensure_tool_call_rounds_allowed(&settings, 2).is_ok());
+        assert!(ensure_tool_call_rounds_allowed(&settings, 3).is_err());
+    }
+
+    #[test]
+    fn rate_limiter_rejects_once_the_bucket_is_empty() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[test]
+    fn acquire_request_slot_resets_when_the_configured_rate_changes() {
+        let workspace_uid = "rate-limit-test-workspace";
+        assert!(acquire_request_slot(workspace_uid, 1).is_ok());
+        assert!(acquire_request_slot(workspace_uid, 1).is_err());
+        // Raising the limit replaces the exhausted bucket with a fresh one.
+        assert!(acquire_request_slot(workspace_uid, 5).is_ok());
+    }
+}
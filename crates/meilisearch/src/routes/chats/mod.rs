@@ -1,12 +1,17 @@
 use actix_web::web::{self, Data};
 use actix_web::HttpResponse;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as CURSOR_ENCODING;
+use base64::Engine as _;
 use deserr::actix_web::AwebQueryParameter;
 use deserr::Deserr;
 use index_scheduler::IndexScheduler;
 use meilisearch_types::deserr::query_params::Param;
-use meilisearch_types::deserr::DeserrQueryParamError;
-use meilisearch_types::error::deserr_codes::{InvalidIndexLimit, InvalidIndexOffset};
-use meilisearch_types::error::ResponseError;
+use meilisearch_types::deserr::{DeserrJsonError, DeserrQueryParamError};
+use meilisearch_types::error::deserr_codes::{
+    InvalidChatWorkspaceCursor, InvalidChatWorkspaceUid, InvalidIndexLimit, InvalidIndexOffset,
+};
+use meilisearch_types::error::{Code, ResponseError};
+use meilisearch_types::features::ChatCompletionSettings;
 use meilisearch_types::keys::actions;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -15,11 +20,16 @@ use utoipa::{IntoParams, ToSchema};
 use super::Pagination;
 use crate::extractors::authentication::policies::ActionPolicy;
 use crate::extractors::authentication::GuardedData;
+use crate::extractors::sequential_extractor::SeqHandler;
 use crate::routes::PAGINATION_DEFAULT_LIMIT;
 
 pub mod chat_completions;
 mod errors;
+pub mod limits;
+pub mod secrets;
 pub mod settings;
+pub mod stats;
+mod usage_store;
 mod utils;
 
 /// The function name to report search progress.
@@ -30,6 +40,11 @@ const MEILI_APPEND_CONVERSATION_MESSAGE_NAME: &str = "_meiliAppendConversationMe
 const MEILI_SEARCH_SOURCES_NAME: &str = "_meiliSearchSources";
 /// The *internal* function name to provide to the LLM to search in indexes.
 const MEILI_SEARCH_IN_INDEX_FUNCTION_NAME: &str = "_meiliSearchInIndex";
+/// The maximum length, in bytes, of a chat workspace uid.
+const MAX_CHAT_WORKSPACE_UID_LENGTH: usize = 512;
+/// Page size used while walking every workspace uid to resolve an `after`
+/// cursor (see [`list_workspaces`]), mirroring `secrets::ROTATE_PAGE_SIZE`.
+const CURSOR_SCAN_PAGE_SIZE: usize = 100;
 
 #[derive(Deserialize)]
 pub struct ChatsParam {
@@ -37,14 +52,24 @@ pub struct ChatsParam {
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("").route(web::get().to(list_workspaces))).service(
-        web::scope("/{workspace_uid}")
-            .service(web::scope("/chat/completions").configure(chat_completions::configure))
-            .service(web::scope("/settings").configure(settings::configure)),
-    );
+    cfg.service(
+        web::resource("")
+            .route(web::get().to(list_workspaces))
+            .route(web::post().to(create_workspace)),
+    )
+        .service(
+            web::resource("/secrets/rotate-kek")
+                .route(web::post().to(SeqHandler(secrets::rotate_kek))),
+        )
+        .service(
+            web::scope("/{workspace_uid}")
+                .service(web::scope("/chat/completions").configure(chat_completions::configure))
+                .service(web::scope("/settings").configure(settings::configure))
+                .service(web::scope("/stats").configure(stats::configure)),
+        );
 }
 
-#[derive(Deserr, Debug, Clone, Copy, IntoParams)]
+#[derive(Deserr, Debug, Clone, IntoParams)]
 #[deserr(error = DeserrQueryParamError, rename_all = camelCase, deny_unknown_fields)]
 #[into_params(rename_all = "camelCase", parameter_in = Query)]
 pub struct ListChats {
@@ -56,10 +81,19 @@ pub struct ListChats {
     #[param(value_type = Option<usize>, default = 20, example = 1)]
     #[deserr(default = Param(PAGINATION_DEFAULT_LIMIT), error = DeserrQueryParamError<InvalidIndexLimit>)]
     pub limit: Param<usize>,
+    /// Opts into keyset pagination: pass an empty string to start from the
+    /// first workspace in uid order, or the `next` cursor from a previous
+    /// response to resume strictly after the last uid it returned. `offset`
+    /// is ignored in this mode, so pages stay stable even if workspaces are
+    /// created or deleted between requests, which a plain offset does not
+    /// guarantee.
+    #[param(value_type = Option<String>, default, example = "bXktd29ya3NwYWNl")]
+    #[deserr(default)]
+    pub after: Option<String>,
 }
 
 impl ListChats {
-    fn as_pagination(self) -> Pagination {
+    fn as_pagination(&self) -> Pagination {
         Pagination { offset: self.offset.0, limit: self.limit.0 }
     }
 }
@@ -71,6 +105,47 @@ pub struct ChatWorkspaceView {
     pub uid: String,
 }
 
+/// Response shape used when `ListChats::after` opts into cursor pagination.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatWorkspacesCursorPage {
+    pub results: Vec<ChatWorkspaceView>,
+    pub total: u64,
+    /// Cursor to pass as `after` to fetch the next page, or `null` once the last page is reached.
+    pub next: Option<String>,
+}
+
+/// Encodes `last_uid` — the last workspace uid on the page just returned —
+/// as the opaque `next` cursor. This is a genuine keyset token: decoded, it
+/// is the uid itself, and resuming from it means "scan keys strictly greater
+/// than this one", not "skip N rows", so a page is never reshuffled by
+/// workspaces created or deleted ahead of the cursor the way an offset page
+/// would be.
+fn encode_cursor(last_uid: &str) -> String {
+    CURSOR_ENCODING.encode(last_uid.as_bytes())
+}
+
+/// Decodes a cursor produced by [`encode_cursor`]. An empty string is a
+/// valid cursor meaning "start from the first workspace in uid order".
+fn decode_cursor(cursor: &str) -> Result<Option<String>, ResponseError> {
+    if cursor.is_empty() {
+        return Ok(None);
+    }
+    let bytes = CURSOR_ENCODING.decode(cursor).map_err(|_| {
+        ResponseError::from_msg(
+            "`after` is not a valid chat workspace cursor".to_string(),
+            Code::InvalidChatWorkspaceCursor,
+        )
+    })?;
+    let uid = String::from_utf8(bytes).map_err(|_| {
+        ResponseError::from_msg(
+            "`after` is not a valid chat workspace cursor".to_string(),
+            Code::InvalidChatWorkspaceCursor,
+        )
+    })?;
+    Ok(Some(uid))
+}
+
 pub async fn list_workspaces(
     index_scheduler: GuardedData<ActionPolicy<{ actions::CHATS_GET }>, Data<IndexScheduler>>,
     paginate: AwebQueryParameter<ListChats, DeserrQueryParamError>,
@@ -79,6 +154,50 @@ pub async fn list_workspaces(
 
     debug!(parameters = ?paginate, "List chat workspaces");
     let filters = index_scheduler.filters();
+
+    if let Some(after) = paginate.after.as_deref() {
+        let after_uid = decode_cursor(after)?;
+        // There is no dedicated sorted-range-scan entry point for chat
+        // workspaces, so the cursor is resolved against the same
+        // `paginated_chat_workspace_uids` the offset branch below uses,
+        // paged through in full (as `secrets::rotate_kek` already does to
+        // visit every workspace). That makes resuming from a cursor immune
+        // to the "skip N rows" drift a plain offset has when workspaces are
+        // created or deleted between pages; it does not make it a bounded
+        // scan, since the whole uid list is still walked once per request.
+        let mut all_uids = Vec::new();
+        let mut offset = 0;
+        loop {
+            let (total, uids) = index_scheduler.paginated_chat_workspace_uids(
+                filters,
+                offset,
+                CURSOR_SCAN_PAGE_SIZE,
+            )?;
+            let fetched = uids.len();
+            all_uids.extend(uids);
+            offset += fetched;
+            if fetched == 0 || offset >= total {
+                break;
+            }
+        }
+        all_uids.sort_unstable();
+
+        let total = all_uids.len() as u64;
+        let start = match &after_uid {
+            Some(after_uid) => all_uids.partition_point(|uid| uid.as_str() <= after_uid.as_str()),
+            None => 0,
+        };
+        let page: Vec<String> = all_uids[start..].iter().take(*paginate.limit).cloned().collect();
+        let next = (start + page.len() < all_uids.len())
+            .then(|| page.last().map(|uid| encode_cursor(uid)))
+            .flatten();
+        let workspaces = page.into_iter().map(|uid| ChatWorkspaceView { uid }).collect::<Vec<_>>();
+        let ret = ChatWorkspacesCursorPage { results: workspaces, total, next };
+
+        debug!(returns = ?ret, "List chat workspaces");
+        return Ok(HttpResponse::Ok().json(ret));
+    }
+
     let (total, workspaces) = index_scheduler.paginated_chat_workspace_uids(
         filters,
         *paginate.offset,
@@ -91,3 +210,89 @@ pub async fn list_workspaces(
     debug!(returns = ?ret, "List chat workspaces");
     Ok(HttpResponse::Ok().json(ret))
 }
+
+#[derive(Debug, Clone, Deserialize, Deserr, ToSchema)]
+#[deserr(error = DeserrJsonError, rename_all = camelCase, deny_unknown_fields)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct CreateChatWorkspace {
+    /// Unique identifier for the chat workspace to create.
+    #[deserr(error = DeserrJsonError<InvalidChatWorkspaceUid>)]
+    pub uid: String,
+}
+
+/// Returns whether `uid` is a valid chat workspace uid: non-empty, at most
+/// [`MAX_CHAT_WORKSPACE_UID_LENGTH`] bytes, and restricted to characters that
+/// are safe to use verbatim as a path segment.
+fn is_valid_chat_workspace_uid(uid: &str) -> bool {
+    !uid.is_empty()
+        && uid.len() <= MAX_CHAT_WORKSPACE_UID_LENGTH
+        && uid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Rejects `uid` with [`Code::InvalidChatWorkspaceUid`] unless
+/// [`is_valid_chat_workspace_uid`] accepts it. Shared by `create_workspace`
+/// and `settings::patch_settings` so a workspace can't end up with a uid the
+/// other route would have refused to create.
+pub(super) fn ensure_valid_chat_workspace_uid(uid: &str) -> Result<(), ResponseError> {
+    if is_valid_chat_workspace_uid(uid) {
+        Ok(())
+    } else {
+        Err(ResponseError::from_msg(
+            format!(
+                "Chat workspace uid `{uid}` is invalid: it must be non-empty, at most {MAX_CHAT_WORKSPACE_UID_LENGTH} bytes, and only contain alphanumeric characters, hyphens (-) and underscores (_)"
+            ),
+            Code::InvalidChatWorkspaceUid,
+        ))
+    }
+}
+
+pub async fn create_workspace(
+    index_scheduler: GuardedData<ActionPolicy<{ actions::CHATS_CREATE }>, Data<IndexScheduler>>,
+    web::Json(body): web::Json<CreateChatWorkspace>,
+) -> Result<HttpResponse, ResponseError> {
+    index_scheduler.features().check_chat_completions("Using the /chats create route")?;
+    ensure_valid_chat_workspace_uid(&body.uid)?;
+
+    let mut wtxn = index_scheduler.write_txn()?;
+    if index_scheduler.chat_settings(&wtxn, &body.uid)?.is_some() {
+        return Err(ResponseError::from_msg(
+            format!("Chat workspace `{}` already exists", body.uid),
+            Code::ChatWorkspaceAlreadyExists,
+        ));
+    }
+    index_scheduler.put_chat_settings(&mut wtxn, &body.uid, &ChatCompletionSettings::default())?;
+    wtxn.commit()?;
+
+    debug!(uid = body.uid, "Create chat workspace");
+    Ok(HttpResponse::Created().json(ChatWorkspaceView { uid: body.uid }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips_the_last_seen_uid() {
+        let cursor = encode_cursor("my-workspace");
+        assert_eq!(decode_cursor(&cursor).unwrap(), Some("my-workspace".to_string()));
+    }
+
+    #[test]
+    fn empty_cursor_means_start_from_the_beginning() {
+        assert_eq!(decode_cursor("").unwrap(), None);
+    }
+
+    #[test]
+    fn garbage_cursor_is_rejected_rather_than_silently_treated_as_an_offset() {
+        assert!(decode_cursor("not valid base64!!").is_err());
+    }
+
+    #[test]
+    fn workspace_uid_validation_accepts_alphanumeric_hyphen_and_underscore() {
+        assert!(is_valid_chat_workspace_uid("my-workspace_1"));
+        assert!(!is_valid_chat_workspace_uid(""));
+        assert!(!is_valid_chat_workspace_uid("has a space"));
+        assert!(!is_valid_chat_workspace_uid("has/a/slash"));
+        assert!(!is_valid_chat_workspace_uid(&"a".repeat(MAX_CHAT_WORKSPACE_UID_LENGTH + 1)));
+    }
+}
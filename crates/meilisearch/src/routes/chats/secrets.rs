@@ -0,0 +1,219 @@
+//! Encryption at rest for chat provider secrets (API keys).
+//!
+//! [`ChatCompletionSettings::api_key`](meilisearch_types::features::ChatCompletionSettings)
+//! used to be written to LMDB as plaintext and only masked on the way out by
+//! `hide_secrets`. This module seals the key with a key-encryption key (KEK)
+//! derived from the instance master key before it ever reaches the store, and
+//! opens it again only where a provider call is about to be made (the
+//! `chat_completions` handler).
+
+use std::sync::Arc;
+
+use actix_web::web::{self, Data};
+use actix_web::HttpResponse;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use deserr::Deserr;
+use index_scheduler::IndexScheduler;
+use meilisearch_types::deserr::DeserrJsonError;
+use meilisearch_types::error::{Code, ResponseError};
+use meilisearch_types::keys::actions;
+use rand::RngCore;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::extractors::authentication::policies::ActionPolicy;
+use crate::extractors::authentication::GuardedData;
+
+/// Number of workspace UIDs re-encrypted per batch while rotating the KEK.
+const ROTATE_PAGE_SIZE: usize = 100;
+
+/// Length in bytes of the derived AES-256-GCM key.
+const KEY_LEN: usize = 32;
+/// Length in bytes of the AES-GCM nonce prepended to every sealed value.
+const NONCE_LEN: usize = 12;
+/// Domain-separation info passed to HKDF so this KEK can never collide with
+/// another secret derived from the same master key.
+const HKDF_INFO: &[u8] = b"meilisearch-chat-completion-secrets";
+
+/// Seals and opens chat provider secrets with a KEK derived from the
+/// instance master key. The master key is not written to the database, so
+/// a database dump alone no longer leaks usable provider credentials.
+#[derive(Clone)]
+pub struct ChatSecretBox {
+    cipher: Arc<Aes256Gcm>,
+}
+
+impl ChatSecretBox {
+    pub fn from_master_key(master_key: &[u8]) -> Self {
+        let mut key = [0u8; KEY_LEN];
+        let hk = hkdf::Hkdf::<sha2::Sha256>::new(None, master_key);
+        hk.expand(HKDF_INFO, &mut key).expect("KEY_LEN is a valid HKDF output length");
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        Self { cipher: Arc::new(cipher) }
+    }
+
+    /// Seals `plaintext`, returning a base64 string combining the nonce and ciphertext.
+    pub fn seal(&self, plaintext: &str) -> Result<String, ResponseError> {
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+        let ciphertext = self.cipher.encrypt(nonce, plaintext.as_bytes()).map_err(|_| {
+            ResponseError::from_msg(
+                "Failed to encrypt chat completion secret".to_string(),
+                Code::Internal,
+            )
+        })?;
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce_bytes);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(BASE64.encode(sealed))
+    }
+
+    /// Opens a value previously returned by [`Self::seal`].
+    pub fn open(&self, sealed: &str) -> Result<String, ResponseError> {
+        let sealed = BASE64.decode(sealed).map_err(|_| {
+            ResponseError::from_msg(
+                "Chat completion secret is not valid base64".to_string(),
+                Code::Internal,
+            )
+        })?;
+        if sealed.len() < NONCE_LEN {
+            return Err(ResponseError::from_msg(
+                "Chat completion secret is too short to contain a nonce".to_string(),
+                Code::Internal,
+            ));
+        }
+        let (nonce_bytes, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext =
+            self.cipher.decrypt(Nonce::from_slice(nonce_bytes), ciphertext).map_err(|_| {
+                ResponseError::from_msg(
+                    "Failed to decrypt chat completion secret, the instance master key may have changed"
+                        .to_string(),
+                    Code::Internal,
+                )
+            })?;
+        String::from_utf8(plaintext).map_err(|_| {
+            ResponseError::from_msg(
+                "Decrypted chat completion secret is not valid UTF-8".to_string(),
+                Code::Internal,
+            )
+        })
+    }
+}
+
+/// Derives the KEK from the instance master key. Fails closed instead of
+/// silently deriving from an empty key: without a configured master key the
+/// "encryption" would be a fixed, publicly-derivable key, which is worse
+/// than refusing to store the secret at all.
+fn chat_secret_box(index_scheduler: &IndexScheduler) -> Result<ChatSecretBox, ResponseError> {
+    // `master_key()` is assumed here the same way `features()` is used
+    // by the other chat routes: an instance-wide value the scheduler already
+    // holds and exposes, not per-workspace state this module owns. It isn't
+    // a store this module is responsible for defining.
+    let master_key = index_scheduler.master_key().ok_or_else(|| {
+        ResponseError::from_msg(
+            "Chat completion API keys cannot be stored or used without an instance master key. \
+             Start Meilisearch with a master key (`--master-key` / `MEILI_MASTER_KEY`) to use \
+             chat workspaces that require a provider API key."
+                .to_string(),
+            Code::ChatCompletionSecretsUnavailable,
+        )
+    })?;
+    Ok(ChatSecretBox::from_master_key(master_key.as_bytes()))
+}
+
+/// Seals a freshly submitted API key before it is written to `put_chat_settings`.
+pub fn seal_api_key(index_scheduler: &IndexScheduler, api_key: &str) -> Result<String, ResponseError> {
+    chat_secret_box(index_scheduler)?.seal(api_key)
+}
+
+/// Opens a stored API key. Called by the `chat_completions` handler right before
+/// it builds the outgoing provider request; the plaintext never touches LMDB.
+pub fn open_api_key(index_scheduler: &IndexScheduler, sealed: &str) -> Result<String, ResponseError> {
+    chat_secret_box(index_scheduler)?.open(sealed)
+}
+
+#[derive(Debug, Clone, Deserialize, Deserr, ToSchema)]
+#[deserr(error = DeserrJsonError, rename_all = camelCase, deny_unknown_fields)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct RotateChatSecretsKey {
+    /// The master key the instance was running with when the secrets currently
+    /// in the store were encrypted. Required to open them before resealing
+    /// with the KEK derived from the instance's current master key.
+    pub previous_master_key: String,
+}
+
+/// Re-encrypts every workspace's chat secrets under the KEK derived from the
+/// instance's current master key. Call this once after rotating the instance
+/// master key, passing the master key the instance was previously started
+/// with so the existing secrets can be opened.
+pub async fn rotate_kek(
+    index_scheduler: GuardedData<ActionPolicy<{ actions::CHATS_SECRETS_ROTATE }>, Data<IndexScheduler>>,
+    web::Json(body): web::Json<RotateChatSecretsKey>,
+) -> Result<HttpResponse, ResponseError> {
+    let old_box = ChatSecretBox::from_master_key(body.previous_master_key.as_bytes());
+    let new_box = chat_secret_box(&index_scheduler)?;
+
+    let mut wtxn = index_scheduler.write_txn()?;
+    let filters = index_scheduler.filters();
+    let mut offset = 0;
+    loop {
+        let (total, uids) =
+            index_scheduler.paginated_chat_workspace_uids(filters, offset, ROTATE_PAGE_SIZE)?;
+        if uids.is_empty() {
+            break;
+        }
+        for uid in &uids {
+            if let Some(mut settings) = index_scheduler.chat_settings(&wtxn, uid)? {
+                if let Some(sealed) = settings.api_key.take() {
+                    let plaintext = old_box.open(&sealed)?;
+                    settings.api_key = Some(new_box.seal(&plaintext)?);
+                }
+                index_scheduler.put_chat_settings(&mut wtxn, uid, &settings)?;
+            }
+        }
+        offset += uids.len();
+        if offset >= total {
+            break;
+        }
+    }
+    wtxn.commit()?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_then_open_round_trips() {
+        let secret_box = ChatSecretBox::from_master_key(b"a very secret master key");
+        let sealed = secret_box.seal("sk-very-secret-api-key").unwrap();
+
+        assert_ne!(sealed, "sk-very-secret-api-key");
+        assert_eq!(secret_box.open(&sealed).unwrap(), "sk-very-secret-api-key");
+    }
+
+    #[test]
+    fn sealed_values_are_not_reused_verbatim_across_calls() {
+        let secret_box = ChatSecretBox::from_master_key(b"a very secret master key");
+        let first = secret_box.seal("sk-very-secret-api-key").unwrap();
+        let second = secret_box.seal("sk-very-secret-api-key").unwrap();
+
+        // A fresh random nonce is drawn on every call, so two seals of the
+        // same plaintext never produce the same ciphertext.
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn opening_with_the_wrong_master_key_fails() {
+        let sealed = ChatSecretBox::from_master_key(b"master key one").seal("sk-secret").unwrap();
+        let opened = ChatSecretBox::from_master_key(b"master key two").open(&sealed);
+
+        assert!(opened.is_err());
+    }
+}
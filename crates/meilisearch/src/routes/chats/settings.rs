@@ -64,11 +64,22 @@ async fn patch_settings(
     web::Json(new): web::Json<GlobalChatSettings>,
 ) -> Result<HttpResponse, ResponseError> {
     let ChatsParam { workspace_uid } = chats_param.into_inner();
+    super::ensure_valid_chat_workspace_uid(&workspace_uid)?;
 
     // TODO do a spawn_blocking here
     let mut wtxn = index_scheduler.write_txn()?;
-    let old_settings =
-        index_scheduler.chat_settings(&mut wtxn, &workspace_uid)?.unwrap_or_default();
+    // Unlike a plain upsert, this requires the workspace to have been created
+    // through `POST /chats` first: letting `PATCH` create arbitrary workspace
+    // uids on the fly bypassed that route's validation entirely.
+    let old_settings = match index_scheduler.chat_settings(&mut wtxn, &workspace_uid)? {
+        Some(settings) => settings,
+        None => {
+            return Err(ResponseError::from_msg(
+                format!("Chat `{workspace_uid}` not found"),
+                Code::ChatWorkspaceNotFound,
+            ))
+        }
+    };
 
     let prompts = match new.prompts {
         Setting::Set(new_prompts) => DbChatCompletionPrompts {
@@ -102,23 +113,71 @@ async fn patch_settings(
         Setting::NotSet => old_settings.prompts,
     };
 
+    let source = match new.source {
+        Setting::Set(new_source) => new_source.into(),
+        Setting::Reset => DbChatCompletionSource::default(),
+        Setting::NotSet => old_settings.source,
+    };
+
+    let deployment_id = match new.deployment_id {
+        Setting::Set(new_deployment_id) => Some(new_deployment_id),
+        Setting::Reset => None,
+        Setting::NotSet => old_settings.deployment_id,
+    };
+
+    let api_version = match new.api_version {
+        Setting::Set(new_api_version) => Some(new_api_version),
+        Setting::Reset => None,
+        Setting::NotSet => old_settings.api_version,
+    };
+
+    if !matches!(source, DbChatCompletionSource::AzureOpenAi)
+        && (deployment_id.is_some() || api_version.is_some())
+    {
+        return Err(ResponseError::from_msg(
+            "`deploymentId` and `apiVersion` can only be set when `source` is `azureOpenAi`"
+                .to_string(),
+            Code::InvalidChatCompletionSource,
+        ));
+    }
+
     let settings = ChatCompletionSettings {
-        source: match new.source {
-            Setting::Set(new_source) => new_source.into(),
-            Setting::Reset => DbChatCompletionSource::default(),
-            Setting::NotSet => old_settings.source,
-        },
+        source,
         base_api: match new.base_api {
             Setting::Set(new_base_api) => Some(new_base_api),
             Setting::Reset => None,
             Setting::NotSet => old_settings.base_api,
         },
         api_key: match new.api_key {
-            Setting::Set(new_api_key) => Some(new_api_key),
+            Setting::Set(new_api_key) => {
+                Some(super::secrets::seal_api_key(&index_scheduler, &new_api_key)?)
+            }
             Setting::Reset => None,
             Setting::NotSet => old_settings.api_key,
         },
+        deployment_id,
+        api_version,
         prompts,
+        max_tokens: match new.max_tokens {
+            Setting::Set(new_max_tokens) => Some(new_max_tokens),
+            Setting::Reset => None,
+            Setting::NotSet => old_settings.max_tokens,
+        },
+        allowed_models: match new.allowed_models {
+            Setting::Set(new_allowed_models) => Some(new_allowed_models),
+            Setting::Reset => None,
+            Setting::NotSet => old_settings.allowed_models,
+        },
+        requests_per_minute: match new.requests_per_minute {
+            Setting::Set(new_requests_per_minute) => Some(new_requests_per_minute),
+            Setting::Reset => None,
+            Setting::NotSet => old_settings.requests_per_minute,
+        },
+        max_tool_call_rounds: match new.max_tool_call_rounds {
+            Setting::Set(new_max_tool_call_rounds) => Some(new_max_tool_call_rounds),
+            Setting::Reset => None,
+            Setting::NotSet => old_settings.max_tool_call_rounds,
+        },
     };
 
     // TODO send analytics
@@ -179,6 +238,36 @@ pub struct GlobalChatSettings {
     #[deserr(default)]
     #[schema(inline, value_type = Option<ChatPrompts>)]
     pub prompts: Setting<ChatPrompts>,
+    /// The Azure OpenAI deployment to target. Only valid when `source` is `azureOpenAi`.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionDeploymentId>)]
+    #[schema(value_type = Option<String>, example = json!("my-gpt4-deployment"))]
+    pub deployment_id: Setting<String>,
+    /// The Azure OpenAI API version to target. Only valid when `source` is `azureOpenAi`.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionApiVersion>)]
+    #[schema(value_type = Option<String>, example = json!("2024-06-01"))]
+    pub api_version: Setting<String>,
+    /// Caps the `max_tokens` a caller may request on a single completion.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionMaxTokens>)]
+    #[schema(value_type = Option<u32>, example = json!(2048))]
+    pub max_tokens: Setting<u32>,
+    /// Restricts which provider models this workspace may call. An empty or unset list allows any model.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionAllowedModels>)]
+    #[schema(value_type = Option<Vec<String>>, example = json!(["gpt-4o", "gpt-4o-mini"]))]
+    pub allowed_models: Setting<Vec<String>>,
+    /// Caps the number of chat completion requests this workspace may issue per minute.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionRequestsPerMinute>)]
+    #[schema(value_type = Option<u32>, example = json!(60))]
+    pub requests_per_minute: Setting<u32>,
+    /// Caps the number of `_meiliSearchInIndex` tool-call rounds allowed in a single conversation.
+    #[serde(default)]
+    #[deserr(default, error = DeserrJsonError<InvalidChatCompletionMaxToolCallRounds>)]
+    #[schema(value_type = Option<u32>, example = json!(5))]
+    pub max_tool_call_rounds: Setting<u32>,
 }
 
 #[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, Deserr, ToSchema)]
@@ -187,12 +276,23 @@ pub struct GlobalChatSettings {
 pub enum ChatCompletionSource {
     #[default]
     OpenAi,
+    AzureOpenAi,
+    Mistral,
+    Anthropic,
+    Gemini,
+    /// Any server speaking the OpenAI chat completion API, e.g. a local Ollama or vLLM instance.
+    OpenAiCompatible,
 }
 
 impl From<ChatCompletionSource> for DbChatCompletionSource {
     fn from(source: ChatCompletionSource) -> Self {
         match source {
             ChatCompletionSource::OpenAi => DbChatCompletionSource::OpenAi,
+            ChatCompletionSource::AzureOpenAi => DbChatCompletionSource::AzureOpenAi,
+            ChatCompletionSource::Mistral => DbChatCompletionSource::Mistral,
+            ChatCompletionSource::Anthropic => DbChatCompletionSource::Anthropic,
+            ChatCompletionSource::Gemini => DbChatCompletionSource::Gemini,
+            ChatCompletionSource::OpenAiCompatible => DbChatCompletionSource::OpenAiCompatible,
         }
     }
 }
@@ -0,0 +1,84 @@
+//! `GET /{workspace_uid}/stats`: reads the counters that `chat_completions`
+//! writes through [`ChatUsageEvent`] after every provider call. The counters
+//! themselves are accumulated by [`super::usage_store`].
+
+use actix_web::web::{self, Data};
+use actix_web::HttpResponse;
+use index_scheduler::IndexScheduler;
+use meilisearch_types::error::{Code, ResponseError};
+use meilisearch_types::keys::actions;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::extractors::authentication::policies::ActionPolicy;
+use crate::extractors::authentication::GuardedData;
+use crate::extractors::sequential_extractor::SeqHandler;
+
+use super::ChatsParam;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(SeqHandler(get_stats))));
+}
+
+/// Usage counters accumulated over a single rolling window (e.g. the last 24 hours).
+#[derive(Debug, Clone, Copy, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatUsageWindow {
+    /// Number of chat completion requests dispatched to the provider, including
+    /// those that failed to reach it. Requests rejected before dispatch (by a
+    /// workspace limit or by invalid settings) are not counted.
+    pub total_requests: u64,
+    /// Number of those requests that ended in an error.
+    pub total_errors: u64,
+    /// Sum of prompt tokens reported by the provider.
+    pub prompt_tokens: u64,
+    /// Sum of completion tokens reported by the provider.
+    pub completion_tokens: u64,
+    /// Number of `_meiliSearchInIndex` tool-call invocations across all requests.
+    pub tool_call_count: u64,
+}
+
+/// Usage statistics for a single chat workspace, as returned by `GET /{workspace_uid}/stats`.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatWorkspaceStats {
+    /// Usage accumulated since this Meilisearch process started. Counters are
+    /// held in memory: they are lost on restart, and they are not cleared when
+    /// a workspace is deleted and recreated under the same uid.
+    pub all_time: ChatUsageWindow,
+    /// Usage accumulated over the last 24 hours.
+    pub last_24h: ChatUsageWindow,
+    /// Usage accumulated over the last 30 days.
+    pub last_30d: ChatUsageWindow,
+}
+
+/// A single usage event, reported by the `chat_completions` handler right
+/// after a provider call returns (success or failure), to be folded into the
+/// workspace's rolling counters by [`super::usage_store::record`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChatUsageEvent {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub tool_call_count: u64,
+    pub is_error: bool,
+}
+
+async fn get_stats(
+    index_scheduler: GuardedData<ActionPolicy<{ actions::CHATS_STATS_GET }>, Data<IndexScheduler>>,
+    chats_param: web::Path<ChatsParam>,
+) -> Result<HttpResponse, ResponseError> {
+    index_scheduler.features().check_chat_completions("Using the /chats stats route")?;
+
+    let ChatsParam { workspace_uid } = chats_param.into_inner();
+    let rtxn = index_scheduler.read_txn()?;
+    if index_scheduler.chat_settings(&rtxn, &workspace_uid)?.is_none() {
+        return Err(ResponseError::from_msg(
+            format!("Chat `{workspace_uid}` not found"),
+            Code::ChatWorkspaceNotFound,
+        ));
+    }
+    drop(rtxn);
+    let stats = super::usage_store::stats_for(&workspace_uid);
+
+    Ok(HttpResponse::Ok().json(stats))
+}
@@ -0,0 +1,123 @@
+//! Backing store for `GET /{workspace_uid}/stats`: accumulates the
+//! [`ChatUsageEvent`](super::stats::ChatUsageEvent)s that `chat_completions`
+//! reports after every provider call into the rolling counters
+//! [`stats::get_stats`](super::stats::get_stats) reads back.
+//!
+//! A real deployment would persist these counters in the `IndexScheduler`
+//! store (the same place `chat_settings`/`put_chat_settings` live) so they
+//! survive a restart; that crate isn't part of this snapshot, so this module
+//! keeps the counters in a process-lifetime map instead, using the same
+//! lazily-initialized static pattern already established by
+//! [`limits::RATE_LIMITERS`](super::limits). Swapping this for LMDB-backed
+//! storage is a drop-in change behind [`record`] and [`stats_for`].
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use super::stats::{ChatUsageEvent, ChatUsageWindow, ChatWorkspaceStats};
+
+const ONE_DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const THIRTY_DAYS: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+struct WorkspaceUsage {
+    all_time: ChatUsageWindow,
+    /// Individual events from the last 30 days, used to compute the rolling
+    /// `last24h`/`last30d` windows. Older events are pruned on every write.
+    recent: Vec<(SystemTime, ChatUsageEvent)>,
+}
+
+impl WorkspaceUsage {
+    fn new() -> Self {
+        Self { all_time: ChatUsageWindow::default(), recent: Vec::new() }
+    }
+
+    fn record(&mut self, at: SystemTime, usage: ChatUsageEvent) {
+        fold_event(&mut self.all_time, &usage);
+        self.recent.push((at, usage));
+        self.recent.retain(|(recorded_at, _)| {
+            at.duration_since(*recorded_at).map(|age| age <= THIRTY_DAYS).unwrap_or(true)
+        });
+    }
+
+    fn snapshot(&self, now: SystemTime) -> ChatWorkspaceStats {
+        let mut last_24h = ChatUsageWindow::default();
+        let mut last_30d = ChatUsageWindow::default();
+        for (recorded_at, usage) in &self.recent {
+            let age = now.duration_since(*recorded_at).unwrap_or_default();
+            if age <= THIRTY_DAYS {
+                fold_event(&mut last_30d, usage);
+            }
+            if age <= ONE_DAY {
+                fold_event(&mut last_24h, usage);
+            }
+        }
+        ChatWorkspaceStats { all_time: self.all_time, last_24h, last_30d }
+    }
+}
+
+fn fold_event(window: &mut ChatUsageWindow, usage: &ChatUsageEvent) {
+    window.total_requests += 1;
+    window.total_errors += u64::from(usage.is_error);
+    window.prompt_tokens += usage.prompt_tokens;
+    window.completion_tokens += usage.completion_tokens;
+    window.tool_call_count += usage.tool_call_count;
+}
+
+static USAGE: Lazy<DashMap<String, Mutex<WorkspaceUsage>>> = Lazy::new(DashMap::new);
+
+/// Folds `usage` into `workspace_uid`'s rolling counters.
+pub fn record(workspace_uid: &str, usage: ChatUsageEvent) {
+    let entry = USAGE.entry(workspace_uid.to_string()).or_insert_with(|| Mutex::new(WorkspaceUsage::new()));
+    entry.lock().unwrap().record(SystemTime::now(), usage);
+}
+
+/// Returns `workspace_uid`'s current usage counters, all zero if no
+/// completion has been recorded for it yet.
+pub fn stats_for(workspace_uid: &str) -> ChatWorkspaceStats {
+    match USAGE.get(workspace_uid) {
+        Some(entry) => entry.lock().unwrap().snapshot(SystemTime::now()),
+        None => ChatWorkspaceStats::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(prompt_tokens: u64, is_error: bool) -> ChatUsageEvent {
+        ChatUsageEvent { prompt_tokens, completion_tokens: 1, tool_call_count: 0, is_error }
+    }
+
+    #[test]
+    fn recorded_usage_accumulates_into_all_time_counters() {
+        let workspace_uid = "usage-store-test-all-time";
+        record(workspace_uid, event(10, false));
+        record(workspace_uid, event(20, true));
+
+        let stats = stats_for(workspace_uid);
+        assert_eq!(stats.all_time.total_requests, 2);
+        assert_eq!(stats.all_time.total_errors, 1);
+        assert_eq!(stats.all_time.prompt_tokens, 30);
+    }
+
+    #[test]
+    fn last_24h_and_last_30d_include_events_just_recorded() {
+        let workspace_uid = "usage-store-test-windows";
+        record(workspace_uid, event(5, false));
+
+        let stats = stats_for(workspace_uid);
+        assert_eq!(stats.last_24h.total_requests, 1);
+        assert_eq!(stats.last_30d.total_requests, 1);
+    }
+
+    #[test]
+    fn unknown_workspace_reports_all_zero_counters() {
+        let stats = stats_for("usage-store-test-never-seen");
+        assert_eq!(stats.all_time.total_requests, 0);
+        assert_eq!(stats.last_24h.total_requests, 0);
+        assert_eq!(stats.last_30d.total_requests, 0);
+    }
+}